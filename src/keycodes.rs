@@ -1,4 +1,4 @@
-#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Copy, Hash)]
+#[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Hash)]
 pub enum KeyCode {
     Backspace,
     Enter,
@@ -15,6 +15,13 @@ pub enum KeyCode {
     Delete,
     Insert,
     Char(char),
+    /// A character typed while holding Ctrl, e.g. `Ctrl('r')` for Ctrl-R.
+    Ctrl(char),
+    /// A character typed while holding Alt/Meta, e.g. `Alt('f')` for Alt-F.
+    Alt(char),
+    /// An entire bracketed-paste payload, delivered as one event so it can
+    /// bypass per-key command processing.
+    Paste(String),
     Null,
     Esc,
 }
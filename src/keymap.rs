@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use crate::keycodes::KeyCode;
+
+/// An editing command that a key event can be bound to. `PromptReader`
+/// dispatches through these rather than matching raw `KeyCode`s, so behavior
+/// stays the same no matter how a command is bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmd {
+    InsertChar(char),
+    Backspace,
+    DeleteForward,
+    MoveLeft,
+    MoveRight,
+    MoveWordLeft,
+    MoveWordRight,
+    MoveHome,
+    MoveEnd,
+    HistoryPrev,
+    HistoryNext,
+    ReverseSearch,
+    KillLine,
+    KillLineStart,
+    KillWordBack,
+    Yank,
+    YankPop,
+    Complete,
+    Submit,
+    Undo,
+    Redo,
+    Noop,
+}
+
+/// Maps key events to the `Cmd` they trigger. Keys with no entry fall back to
+/// `Cmd::InsertChar` for plain characters and `Cmd::Noop` otherwise.
+#[derive(Debug, Clone)]
+pub struct Keymap(HashMap<KeyCode, Cmd>);
+
+impl Keymap {
+    /// An empty keymap; plain characters insert themselves and every other
+    /// key does nothing.
+    pub fn empty() -> Self {
+        Keymap(HashMap::new())
+    }
+
+    /// The default Emacs-like bindings: Ctrl-A/E home/end, Ctrl-K/U kill to
+    /// end/start of line, Ctrl-W kill-word, Ctrl-Y/Alt-Y yank/yank-pop,
+    /// Alt-F/B word motion, Ctrl-R reverse search, Ctrl-_/Alt-_ undo/redo.
+    pub fn emacs() -> Self {
+        let mut map = HashMap::new();
+        map.insert(KeyCode::Backspace, Cmd::Backspace);
+        map.insert(KeyCode::Delete, Cmd::DeleteForward);
+        map.insert(KeyCode::Left, Cmd::MoveLeft);
+        map.insert(KeyCode::Right, Cmd::MoveRight);
+        map.insert(KeyCode::Home, Cmd::MoveHome);
+        map.insert(KeyCode::End, Cmd::MoveEnd);
+        map.insert(KeyCode::Up, Cmd::HistoryPrev);
+        map.insert(KeyCode::Down, Cmd::HistoryNext);
+        map.insert(KeyCode::Tab, Cmd::Complete);
+        map.insert(KeyCode::Enter, Cmd::Submit);
+        map.insert(KeyCode::Ctrl('a'), Cmd::MoveHome);
+        map.insert(KeyCode::Ctrl('e'), Cmd::MoveEnd);
+        map.insert(KeyCode::Ctrl('k'), Cmd::KillLine);
+        map.insert(KeyCode::Ctrl('u'), Cmd::KillLineStart);
+        map.insert(KeyCode::Ctrl('w'), Cmd::KillWordBack);
+        map.insert(KeyCode::Ctrl('y'), Cmd::Yank);
+        map.insert(KeyCode::Ctrl('r'), Cmd::ReverseSearch);
+        map.insert(KeyCode::Alt('y'), Cmd::YankPop);
+        map.insert(KeyCode::Alt('f'), Cmd::MoveWordRight);
+        map.insert(KeyCode::Alt('b'), Cmd::MoveWordLeft);
+        map.insert(KeyCode::Ctrl('_'), Cmd::Undo);
+        map.insert(KeyCode::Alt('_'), Cmd::Redo);
+        Keymap(map)
+    }
+
+    /// Bind `key` to `cmd`, overriding any existing binding.
+    pub fn bind(&mut self, key: KeyCode, cmd: Cmd) {
+        self.0.insert(key, cmd);
+    }
+
+    /// Resolve `key` to its bound command, if any.
+    pub fn get(&self, key: &KeyCode) -> Option<Cmd> {
+        self.0.get(key).copied()
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::emacs()
+    }
+}
@@ -1,75 +1,698 @@
 pub mod keycodes;
+pub mod keymap;
 
 use keycodes::KeyCode;
+use keymap::{Cmd, Keymap};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Supplies tab-completion candidates for the word under the cursor.
+pub trait Completer {
+    /// Given the full line and the cursor's byte offset into it, return the
+    /// byte offset where replacement should start and the list of candidate
+    /// replacements for the text between that offset and the cursor.
+    fn complete(&self, line: &str, cursor: usize) -> (usize, Vec<String>);
+}
+
+/// A `Completer` that never offers any candidates; the default for a
+/// `PromptReader` built without `with_completer`.
+struct NoopCompleter;
+
+impl Completer for NoopCompleter {
+    fn complete(&self, _line: &str, cursor: usize) -> (usize, Vec<String>) {
+        (cursor, Vec::new())
+    }
+}
+
+/// How `paste` treats control characters (e.g. raw `Ctrl-C`, `Ctrl-D`) found
+/// in pasted text. Newlines and tabs are never filtered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PastePolicy {
+    /// Insert the pasted text exactly as given.
+    Literal,
+    /// Drop control characters (Unicode category Cc, which covers the ASCII
+    /// controls and U+0080..U+009F) other than newline and tab.
+    FilterControl,
+}
 
-#[derive(Debug)]
 pub struct PromptReader {
     result: String,
+    /// Byte offsets of every grapheme boundary in `result`, including 0 and
+    /// `result.len()`. Has `grapheme_count() + 1` entries and is recomputed
+    /// after every edit.
+    boundaries: Vec<usize>,
+    /// Logical cursor position, indexing into `boundaries` (0..=grapheme_count).
     cursor: usize,
     done: bool,
+    /// Previously submitted lines, oldest first.
+    history: Vec<String>,
+    /// Index into `history` currently shown in the buffer, or `None` when the
+    /// buffer holds the in-progress draft rather than a recalled entry.
+    history_index: Option<usize>,
+    /// The in-progress draft, saved when `Up` first navigates away from it.
+    draft: String,
+    /// Active incremental reverse-search state, if Ctrl-R has been pressed.
+    search: Option<SearchState>,
+    /// Bounded ring of killed text, most recently killed last.
+    kill_ring: Vec<String>,
+    /// Direction of the most recent kill, so consecutive kills in the same
+    /// direction coalesce into one ring entry instead of creating a new one.
+    last_kill_dir: Option<KillDir>,
+    /// The region last inserted by a yank, so a following yank-pop knows what
+    /// to replace.
+    last_yank: Option<YankState>,
+    /// Supplies candidates for `KeyCode::Tab`.
+    completer: Box<dyn Completer>,
+    /// Candidates returned by the most recent Tab press, for a host UI to display.
+    last_completions: Vec<String>,
+    /// Maps key events to the `Cmd` they dispatch to.
+    keymap: Keymap,
+    /// Edits applied so far, most recent last; `Undo` pops and reverts one.
+    undo_stack: Vec<UndoRecord>,
+    /// Edits undone so far; `Redo` pops and reapplies one. Cleared by any new edit.
+    redo_stack: Vec<UndoRecord>,
+    /// Whether the last dispatched command was a non-whitespace self-insert,
+    /// so the next one coalesces into the same undo record instead of
+    /// starting a new one.
+    undo_group_open: bool,
+    /// How `paste` treats control characters in the pasted text.
+    paste_policy: PastePolicy,
+}
+
+/// One undoable edit: starting at grapheme index `start`, `removed` was
+/// replaced with `inserted` (both measured in graphemes), moving the cursor
+/// from `cursor_before` to `cursor_after`.
+#[derive(Debug, Clone)]
+struct UndoRecord {
+    start: usize,
+    removed: String,
+    inserted: String,
+    cursor_before: usize,
+    cursor_after: usize,
+}
+
+impl std::fmt::Debug for PromptReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PromptReader")
+            .field("result", &self.result)
+            .field("cursor", &self.cursor)
+            .field("done", &self.done)
+            .field("history", &self.history)
+            .field("kill_ring", &self.kill_ring)
+            .field("last_completions", &self.last_completions)
+            .finish_non_exhaustive()
+    }
+}
+
+const KILL_RING_CAPACITY: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KillDir {
+    Forward,
+    Backward,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct YankState {
+    /// Grapheme index where the yanked text begins.
+    start: usize,
+    /// Grapheme length of the text currently sitting in the buffer from the yank.
+    len: usize,
+    /// How far back from the most recent kill-ring entry the last yank took from.
+    ring_index: usize,
+}
+
+#[derive(Debug)]
+struct SearchState {
+    query: String,
+    /// How many matches (from most recent) to skip before taking the first hit.
+    skip: usize,
+    saved_result: String,
+    saved_cursor: usize,
+}
+
+impl Default for PromptReader {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PromptReader {
     /// Create a new PromptReader initialized with an empty placeholder.
     /// The cursor will be at the start of the string
     pub fn new() -> Self {
-        PromptReader {
+        let mut pr = PromptReader {
             result: "".into(),
+            boundaries: Vec::new(),
             cursor: 0,
             done: false,
-        }
+            history: Vec::new(),
+            history_index: None,
+            draft: String::new(),
+            search: None,
+            kill_ring: Vec::new(),
+            last_kill_dir: None,
+            last_yank: None,
+            completer: Box::new(NoopCompleter),
+            last_completions: Vec::new(),
+            keymap: Keymap::default(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            undo_group_open: false,
+            paste_policy: PastePolicy::Literal,
+        };
+        pr.recompute_boundaries();
+        pr
     }
 
     /// Create a PromptReader with the initial result ph and the cursor at position cursor_pos.
-    /// If `cursor_pos` is None it will be set to the end of the string.
+    /// `cursor_pos` is a grapheme index, not a byte offset. If `cursor_pos` is None it will be
+    /// set to the end of the string.
     pub fn new_with_placeholder(ph: &str, cursor_pos: Option<usize>) -> Self {
-        PromptReader {
+        let mut pr = PromptReader {
             result: ph.into(),
-            cursor: cursor_pos.unwrap_or_else(|| ph.len()),
+            boundaries: Vec::new(),
+            cursor: 0,
             done: false,
+            history: Vec::new(),
+            history_index: None,
+            draft: String::new(),
+            search: None,
+            kill_ring: Vec::new(),
+            last_kill_dir: None,
+            last_yank: None,
+            completer: Box::new(NoopCompleter),
+            last_completions: Vec::new(),
+            keymap: Keymap::default(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            undo_group_open: false,
+            paste_policy: PastePolicy::Literal,
+        };
+        pr.recompute_boundaries();
+        pr.cursor = cursor_pos.unwrap_or_else(|| pr.grapheme_count());
+        pr
+    }
+
+    /// Attach prior entries (oldest first) so `Up`/`Down` can recall them.
+    pub fn with_history(mut self, history: Vec<String>) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Returns the history of previously submitted lines, oldest first.
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Attach a completer so `KeyCode::Tab` invokes it for completion candidates.
+    pub fn with_completer(mut self, completer: Box<dyn Completer>) -> Self {
+        self.completer = completer;
+        self
+    }
+
+    /// Returns the candidates produced by the most recent `Tab` press.
+    pub fn completions(&self) -> &[String] {
+        &self.last_completions
+    }
+
+    /// Choose how `paste` treats control characters in pasted text. Defaults
+    /// to `PastePolicy::Literal`.
+    pub fn with_paste_policy(mut self, policy: PastePolicy) -> Self {
+        self.paste_policy = policy;
+        self
+    }
+
+    /// Insert `text` at the cursor in one operation, as bracketed paste mode
+    /// delivers it. Unlike `next_key`, newlines and control characters in
+    /// `text` are inserted as literal data rather than triggering `Enter` or
+    /// other command handling. The whole paste is one undo group.
+    pub fn paste(&mut self, text: &str) {
+        let filtered;
+        let text = match self.paste_policy {
+            PastePolicy::Literal => text,
+            PastePolicy::FilterControl => {
+                filtered = text
+                    .chars()
+                    .filter(|c| *c == '\n' || *c == '\t' || !c.is_control())
+                    .collect::<String>();
+                filtered.as_str()
+            }
+        };
+        let start = self.cursor;
+        let offset = self.cursor_byte_offset();
+        self.insert_at_cursor(text);
+        self.cursor = self.grapheme_index_of_byte(offset + text.len());
+        self.push_undo(start, String::new(), text.to_string(), start, self.cursor);
+        self.undo_group_open = false;
+    }
+
+    /// Recompute the cached grapheme boundary offsets for `result`. Must be
+    /// called after any mutation of `result`.
+    fn recompute_boundaries(&mut self) {
+        self.boundaries = self
+            .result
+            .grapheme_indices(true)
+            .map(|(i, _)| i)
+            .chain(std::iter::once(self.result.len()))
+            .collect();
+    }
+
+    /// Number of grapheme clusters currently in the buffer.
+    fn grapheme_count(&self) -> usize {
+        self.boundaries.len() - 1
+    }
+
+    /// Byte offset in `result` that the cursor currently points at.
+    fn cursor_byte_offset(&self) -> usize {
+        self.boundaries[self.cursor]
+    }
+
+    /// The grapheme cluster at logical index `i`.
+    fn grapheme_at(&self, i: usize) -> &str {
+        &self.result[self.boundaries[i]..self.boundaries[i + 1]]
+    }
+
+    fn is_word_char(grapheme: &str) -> bool {
+        !grapheme.chars().next().is_some_and(char::is_whitespace)
+    }
+
+    /// Grapheme index reached by skipping one run of whitespace then one run
+    /// of word characters forward from `from`.
+    fn word_right_of(&self, from: usize) -> usize {
+        let n = self.grapheme_count();
+        let mut i = from;
+        while i < n && !Self::is_word_char(self.grapheme_at(i)) {
+            i += 1;
+        }
+        while i < n && Self::is_word_char(self.grapheme_at(i)) {
+            i += 1;
+        }
+        i
+    }
+
+    /// Grapheme index reached by skipping one run of whitespace then one run
+    /// of word characters backward from `from`.
+    fn word_left_of(&self, from: usize) -> usize {
+        let mut i = from;
+        while i > 0 && !Self::is_word_char(self.grapheme_at(i - 1)) {
+            i -= 1;
+        }
+        while i > 0 && Self::is_word_char(self.grapheme_at(i - 1)) {
+            i -= 1;
+        }
+        i
+    }
+
+    /// Remove the grapheme range `[start, end)` and return the removed text.
+    fn remove_grapheme_range(&mut self, start: usize, end: usize) -> String {
+        let byte_start = self.boundaries[start];
+        let byte_end = self.boundaries[end];
+        let removed = self.result[byte_start..byte_end].to_string();
+        self.result.replace_range(byte_start..byte_end, "");
+        self.recompute_boundaries();
+        removed
+    }
+
+    /// Push killed `text` onto the kill ring, coalescing with the previous
+    /// entry if the last kill was in the same `dir`ection.
+    fn push_kill(&mut self, text: String, dir: KillDir) {
+        if text.is_empty() {
+            return;
+        }
+        if self.last_kill_dir == Some(dir) {
+            if let Some(top) = self.kill_ring.last_mut() {
+                match dir {
+                    KillDir::Forward => top.push_str(&text),
+                    KillDir::Backward => {
+                        let mut combined = text;
+                        combined.push_str(top);
+                        *top = combined;
+                    }
+                }
+                return;
+            }
+        }
+        self.kill_ring.push(text);
+        if self.kill_ring.len() > KILL_RING_CAPACITY {
+            self.kill_ring.remove(0);
+        }
+    }
+
+    /// The kill-ring entry `ring_index` slots back from the most recent one.
+    fn ring_entry(&self, ring_index: usize) -> Option<&str> {
+        let len = self.kill_ring.len();
+        if len == 0 {
+            return None;
         }
+        Some(&self.kill_ring[len - 1 - (ring_index % len)])
+    }
+
+    /// Insert `text` at the cursor and return its grapheme length.
+    fn insert_at_cursor(&mut self, text: &str) -> usize {
+        let offset = self.cursor_byte_offset();
+        self.result.insert_str(offset, text);
+        self.recompute_boundaries();
+        text.graphemes(true).count()
     }
 
     /// Call this when you recieve a key event and want to pass it to the
     /// PromptReader. It will update its internal state and with based on the keycode input.
     pub fn next_key(&mut self, key_code: KeyCode) {
-        match key_code {
-            KeyCode::Char(c) => {
-                if self.cursor >= self.result.len() {
-                    self.result.push(c);
+        if let KeyCode::Paste(ref text) = key_code {
+            self.paste(text);
+            return;
+        }
+        if self.search.is_some() {
+            self.next_key_searching(key_code);
+            return;
+        }
+        let cmd = self.keymap.get(&key_code).unwrap_or(match key_code {
+            KeyCode::Char(c) => Cmd::InsertChar(c),
+            _ => Cmd::Noop,
+        });
+        self.dispatch(cmd);
+    }
+
+    /// Attach a keymap, overriding the default Emacs-like bindings.
+    pub fn with_keymap(mut self, keymap: Keymap) -> Self {
+        self.keymap = keymap;
+        self
+    }
+
+    /// Run a resolved command against the buffer.
+    fn dispatch(&mut self, cmd: Cmd) {
+        // Consecutive kills (same direction) and yank-pops chain off the
+        // previous command, as does a run of plain self-inserts into one
+        // undo group; anything else breaks all three chains.
+        let group_was_open = self.undo_group_open;
+        self.undo_group_open = false;
+        let mut next_kill_dir = None;
+        let mut next_yank = None;
+        match cmd {
+            Cmd::InsertChar(c) => {
+                let start = self.cursor;
+                let offset = self.cursor_byte_offset();
+                self.result.insert(offset, c);
+                self.recompute_boundaries();
+                self.cursor = self.grapheme_index_of_byte(offset + c.len_utf8());
+                let whitespace = c.is_whitespace();
+                let coalesce = group_was_open
+                    && !whitespace
+                    && self.undo_stack.last().is_some_and(|r| {
+                        r.removed.is_empty()
+                            && r.start + r.inserted.graphemes(true).count() == start
+                    });
+                if coalesce {
+                    let cursor_after = self.cursor;
+                    let top = self.undo_stack.last_mut().unwrap();
+                    top.inserted.push(c);
+                    top.cursor_after = cursor_after;
                 } else {
-                    self.result.insert(self.cursor, c);
+                    self.push_undo(start, String::new(), c.to_string(), start, self.cursor);
                 }
-                self.cursor = self.cursor + 1;
+                self.undo_group_open = !whitespace;
             }
-            KeyCode::Backspace => {
-                if self.cursor != 0 {
-                    self.result.remove(self.cursor - 1);
-                    self.cursor = self.cursor - 1;
-                }
+            Cmd::Backspace if self.cursor != 0 => {
+                let start = self.cursor - 1;
+                let cursor_before = self.cursor;
+                let removed = self.remove_grapheme_range(start, self.cursor);
+                self.cursor -= 1;
+                self.push_undo(start, removed, String::new(), cursor_before, self.cursor);
+            }
+            Cmd::DeleteForward if self.cursor != self.grapheme_count() => {
+                let cursor_before = self.cursor;
+                let removed = self.remove_grapheme_range(self.cursor, self.cursor + 1);
+                self.push_undo(self.cursor, removed, String::new(), cursor_before, self.cursor);
+            }
+            Cmd::MoveLeft if self.cursor != 0 => {
+                self.cursor -= 1;
             }
-            KeyCode::Delete => {
-                if self.cursor != self.result.len() {
-                    self.result.remove(self.cursor);
+            Cmd::MoveRight if self.cursor < self.grapheme_count() => {
+                self.cursor += 1;
+            }
+            Cmd::MoveHome => self.cursor = 0,
+            Cmd::MoveEnd => self.cursor = self.grapheme_count(),
+            Cmd::HistoryPrev => self.recall_older(),
+            Cmd::HistoryNext => self.recall_newer(),
+            Cmd::ReverseSearch => self.start_or_advance_search(),
+            Cmd::MoveWordRight => self.cursor = self.word_right_of(self.cursor),
+            Cmd::MoveWordLeft => self.cursor = self.word_left_of(self.cursor),
+            Cmd::KillLine => {
+                let cursor_before = self.cursor;
+                let text = self.remove_grapheme_range(self.cursor, self.grapheme_count());
+                self.push_undo(self.cursor, text.clone(), String::new(), cursor_before, self.cursor);
+                self.push_kill(text, KillDir::Forward);
+                next_kill_dir = Some(KillDir::Forward);
+            }
+            Cmd::KillLineStart => {
+                let cursor_before = self.cursor;
+                let text = self.remove_grapheme_range(0, self.cursor);
+                self.push_undo(0, text.clone(), String::new(), cursor_before, 0);
+                self.push_kill(text, KillDir::Backward);
+                self.cursor = 0;
+                next_kill_dir = Some(KillDir::Backward);
+            }
+            Cmd::KillWordBack => {
+                let start = self.word_left_of(self.cursor);
+                let cursor_before = self.cursor;
+                let text = self.remove_grapheme_range(start, self.cursor);
+                self.push_undo(start, text.clone(), String::new(), cursor_before, start);
+                self.push_kill(text, KillDir::Backward);
+                self.cursor = start;
+                next_kill_dir = Some(KillDir::Backward);
+            }
+            Cmd::Yank => {
+                if let Some(text) = self.ring_entry(0).map(str::to_string) {
+                    let start = self.cursor;
+                    let len = self.insert_at_cursor(&text);
+                    self.cursor = start + len;
+                    self.push_undo(start, String::new(), text, start, self.cursor);
+                    next_yank = Some(YankState {
+                        start,
+                        len,
+                        ring_index: 0,
+                    });
                 }
             }
-            KeyCode::Left => {
-                if self.cursor != 0 {
-                    self.cursor = self.cursor - 1;
+            Cmd::YankPop => {
+                if let Some(yank) = self.last_yank {
+                    let ring_index = yank.ring_index + 1;
+                    if let Some(text) = self.ring_entry(ring_index).map(str::to_string) {
+                        let cursor_before = self.cursor;
+                        let removed =
+                            self.remove_grapheme_range(yank.start, yank.start + yank.len);
+                        self.cursor = yank.start;
+                        let len = self.insert_at_cursor(&text);
+                        self.cursor = yank.start + len;
+                        self.push_undo(yank.start, removed, text, cursor_before, self.cursor);
+                        next_yank = Some(YankState {
+                            start: yank.start,
+                            len,
+                            ring_index,
+                        });
+                    }
                 }
             }
-            KeyCode::Right => {
-                if self.cursor >= self.result.len() - 1 {
-                    self.cursor = self.cursor + 1;
+            Cmd::Submit => {
+                if self.history.last().map(String::as_str) != Some(self.result.as_str()) {
+                    self.history.push(self.result.clone());
                 }
+                self.history_index = None;
+                self.done = true;
+            }
+            Cmd::Complete => self.complete(),
+            Cmd::Undo => self.undo(),
+            Cmd::Redo => self.redo(),
+            _ => {}
+        }
+        self.last_kill_dir = next_kill_dir;
+        self.last_yank = next_yank;
+    }
+
+    /// Ask the attached `Completer` for candidates at the cursor and insert
+    /// either the sole candidate or the candidates' longest common prefix.
+    fn complete(&mut self) {
+        let cursor_byte = self.cursor_byte_offset();
+        let (start_byte, candidates) = self.completer.complete(&self.result, cursor_byte);
+        let insertion = match candidates.as_slice() {
+            [one] => Some(one.clone()),
+            [_, ..] => Some(longest_common_prefix(&candidates)),
+            [] => None,
+        };
+        self.last_completions = candidates;
+        if let Some(text) = insertion {
+            let start = self.grapheme_index_of_byte(start_byte);
+            let cursor_before = self.cursor;
+            let removed = self.result[start_byte..cursor_byte].to_string();
+            self.result.replace_range(start_byte..cursor_byte, &text);
+            self.recompute_boundaries();
+            self.cursor = self.grapheme_index_of_byte(start_byte + text.len());
+            self.push_undo(start, removed, text, cursor_before, self.cursor);
+        }
+    }
+
+    /// Record a completed edit, discarding any redo history it supersedes.
+    fn push_undo(
+        &mut self,
+        start: usize,
+        removed: String,
+        inserted: String,
+        cursor_before: usize,
+        cursor_after: usize,
+    ) {
+        self.undo_stack.push(UndoRecord {
+            start,
+            removed,
+            inserted,
+            cursor_before,
+            cursor_after,
+        });
+        self.redo_stack.clear();
+    }
+
+    /// Revert the most recent edit, if any, and move it onto the redo stack.
+    fn undo(&mut self) {
+        if let Some(record) = self.undo_stack.pop() {
+            let inserted_len = record.inserted.graphemes(true).count();
+            let start_byte = self.boundaries[record.start];
+            let end_byte = self.boundaries[record.start + inserted_len];
+            self.result.replace_range(start_byte..end_byte, &record.removed);
+            self.recompute_boundaries();
+            self.cursor = record.cursor_before;
+            self.redo_stack.push(record);
+        }
+    }
+
+    /// Reapply the most recently undone edit, if any, and move it back onto
+    /// the undo stack.
+    fn redo(&mut self) {
+        if let Some(record) = self.redo_stack.pop() {
+            let removed_len = record.removed.graphemes(true).count();
+            let start_byte = self.boundaries[record.start];
+            let end_byte = self.boundaries[record.start + removed_len];
+            self.result.replace_range(start_byte..end_byte, &record.inserted);
+            self.recompute_boundaries();
+            self.cursor = record.cursor_after;
+            self.undo_stack.push(record);
+        }
+    }
+
+    /// The grapheme index whose boundary is `byte_offset`.
+    fn grapheme_index_of_byte(&self, byte_offset: usize) -> usize {
+        self.boundaries
+            .binary_search(&byte_offset)
+            .unwrap_or_else(|i| i)
+    }
+
+    /// Handle a key while an incremental reverse-search is active.
+    fn next_key_searching(&mut self, key_code: KeyCode) {
+        if self.keymap.get(&key_code) == Some(Cmd::ReverseSearch) {
+            self.search.as_mut().unwrap().skip += 1;
+            self.apply_search_match();
+            return;
+        }
+        match key_code {
+            KeyCode::Char(c) => {
+                let search = self.search.as_mut().unwrap();
+                search.query.push(c);
+                search.skip = 0;
+                self.apply_search_match();
+            }
+            KeyCode::Backspace => {
+                let search = self.search.as_mut().unwrap();
+                search.query.pop();
+                search.skip = 0;
+                self.apply_search_match();
             }
             KeyCode::Enter => {
-                self.done = true;
+                self.search = None;
+            }
+            KeyCode::Esc => {
+                let search = self.search.take().unwrap();
+                let matched = self.result != search.saved_result;
+                self.result = search.saved_result;
+                self.cursor = search.saved_cursor;
+                self.recompute_boundaries();
+                if matched {
+                    self.clear_undo_history();
+                }
             }
             _ => {}
         }
     }
 
+    /// Find the most recent history entry containing the search query, skipping
+    /// `search.skip` earlier matches, and load it into the buffer if found.
+    fn apply_search_match(&mut self) {
+        let search = self.search.as_ref().unwrap();
+        let hit = self
+            .history
+            .iter()
+            .rev()
+            .filter(|entry| entry.contains(&search.query))
+            .nth(search.skip)
+            .cloned();
+        if let Some(entry) = hit {
+            self.result = entry;
+            self.recompute_boundaries();
+            self.cursor = self.grapheme_count();
+            self.clear_undo_history();
+        }
+    }
+
+    /// Drop all undo/redo records; used whenever the buffer is replaced
+    /// wholesale (history recall, search) rather than edited in place.
+    fn clear_undo_history(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.undo_group_open = false;
+    }
+
+    fn start_or_advance_search(&mut self) {
+        self.search = Some(SearchState {
+            query: String::new(),
+            skip: 0,
+            saved_result: self.result.clone(),
+            saved_cursor: self.cursor,
+        });
+    }
+
+    /// Walk backward into older history entries, saving the draft on first use.
+    fn recall_older(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+        match self.history_index {
+            None => {
+                self.draft = self.result.clone();
+                self.history_index = Some(self.history.len() - 1);
+            }
+            Some(0) => return,
+            Some(i) => self.history_index = Some(i - 1),
+        }
+        self.result = self.history[self.history_index.unwrap()].clone();
+        self.recompute_boundaries();
+        self.cursor = self.grapheme_count();
+        self.clear_undo_history();
+    }
+
+    /// Walk forward through history, returning to the draft past the newest entry.
+    fn recall_newer(&mut self) {
+        let Some(i) = self.history_index else {
+            return;
+        };
+        if i + 1 >= self.history.len() {
+            self.history_index = None;
+            self.result = self.draft.clone();
+        } else {
+            self.history_index = Some(i + 1);
+            self.result = self.history[i + 1].clone();
+        }
+        self.recompute_boundaries();
+        self.cursor = self.grapheme_count();
+        self.clear_undo_history();
+    }
+
     /// Returns true after enter has been sent to the next_key function.
     pub fn done(&self) -> bool {
         self.done
@@ -81,6 +704,23 @@ impl PromptReader {
     }
 }
 
+/// The longest prefix shared by every candidate, compared char-by-char so
+/// multi-byte UTF-8 sequences are never split.
+fn longest_common_prefix(candidates: &[String]) -> String {
+    let chars: Vec<Vec<char>> = candidates.iter().map(|s| s.chars().collect()).collect();
+    let min_len = chars.iter().map(Vec::len).min().unwrap_or(0);
+    let mut prefix = String::new();
+    for i in 0..min_len {
+        let c = chars[0][i];
+        if chars.iter().all(|cs| cs[i] == c) {
+            prefix.push(c);
+        } else {
+            break;
+        }
+    }
+    prefix
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -189,4 +829,417 @@ mod tests {
 
         assert_eq!(pr.result(), "teHejst".to_string());
     }
+
+    #[test]
+    fn multi_byte_char_insert() {
+        let mut pr = PromptReader::new();
+        for c in "café".chars() {
+            pr.next_key(KeyCode::Char(c));
+        }
+        assert_eq!(pr.result(), "café".to_string());
+    }
+
+    #[test]
+    fn multi_byte_backspace_removes_whole_char() {
+        let mut pr = PromptReader::new();
+        for c in "café".chars() {
+            pr.next_key(KeyCode::Char(c));
+        }
+        pr.next_key(KeyCode::Backspace);
+        assert_eq!(pr.result(), "caf".to_string());
+    }
+
+    #[test]
+    fn emoji_left_and_delete() {
+        let mut pr = PromptReader::new();
+        for c in "a😀b".chars() {
+            pr.next_key(KeyCode::Char(c));
+        }
+        pr.next_key(KeyCode::Left);
+        pr.next_key(KeyCode::Left);
+        pr.next_key(KeyCode::Delete);
+        assert_eq!(pr.result(), "ab".to_string());
+    }
+
+    #[test]
+    fn combining_mark_forms_one_grapheme_and_keeps_cursor_in_range() {
+        let mut pr = PromptReader::new();
+        pr.next_key(KeyCode::Char('e'));
+        pr.next_key(KeyCode::Char('\u{0301}'));
+        pr.next_key(KeyCode::Char('x'));
+        assert_eq!(pr.result(), "e\u{0301}x");
+        pr.next_key(KeyCode::Backspace);
+        pr.next_key(KeyCode::Backspace);
+        assert_eq!(pr.result(), "");
+    }
+
+    #[test]
+    fn undo_reverts_a_coalesced_insert_ending_in_a_combining_mark() {
+        let mut pr = PromptReader::new();
+        pr.next_key(KeyCode::Char('e'));
+        pr.next_key(KeyCode::Char('\u{0301}'));
+        pr.next_key(KeyCode::Ctrl('_'));
+        assert_eq!(pr.result(), "");
+    }
+
+    fn submit(pr: &mut PromptReader, line: &str) {
+        for c in line.chars() {
+            pr.next_key(KeyCode::Char(c));
+        }
+        pr.next_key(KeyCode::Enter);
+    }
+
+    #[test]
+    fn history_up_recalls_last_submitted_line() {
+        let mut pr = PromptReader::new();
+        submit(&mut pr, "first");
+        let mut pr = PromptReader::new().with_history(pr.history().to_vec());
+        pr.next_key(KeyCode::Up);
+        assert_eq!(pr.result(), "first");
+    }
+
+    #[test]
+    fn history_up_up_down_returns_to_draft() {
+        let mut pr = PromptReader::new().with_history(vec!["first".into(), "second".into()]);
+        pr.next_key(KeyCode::Char('x'));
+        pr.next_key(KeyCode::Up);
+        pr.next_key(KeyCode::Up);
+        assert_eq!(pr.result(), "first");
+        pr.next_key(KeyCode::Down);
+        assert_eq!(pr.result(), "second");
+        pr.next_key(KeyCode::Down);
+        assert_eq!(pr.result(), "x");
+    }
+
+    #[test]
+    fn history_dedupes_consecutive_duplicates() {
+        let mut pr = PromptReader::new();
+        submit(&mut pr, "same");
+        let mut pr = PromptReader::new().with_history(pr.history().to_vec());
+        submit(&mut pr, "same");
+        assert_eq!(pr.history(), &["same".to_string()]);
+    }
+
+    #[test]
+    fn reverse_search_finds_substring_match() {
+        let mut pr =
+            PromptReader::new().with_history(vec!["git commit".into(), "git push".into()]);
+        pr.next_key(KeyCode::Ctrl('r'));
+        pr.next_key(KeyCode::Char('p'));
+        pr.next_key(KeyCode::Char('u'));
+        assert_eq!(pr.result(), "git push");
+    }
+
+    #[test]
+    fn reverse_search_repeated_ctrl_r_cycles_older() {
+        let mut pr =
+            PromptReader::new().with_history(vec!["git push".into(), "git pull".into()]);
+        pr.next_key(KeyCode::Ctrl('r'));
+        pr.next_key(KeyCode::Char('g'));
+        assert_eq!(pr.result(), "git pull");
+        pr.next_key(KeyCode::Ctrl('r'));
+        assert_eq!(pr.result(), "git push");
+    }
+
+    #[test]
+    fn reverse_search_esc_restores_pre_search_buffer() {
+        let mut pr = PromptReader::new().with_history(vec!["git commit".into()]);
+        pr.next_key(KeyCode::Char('x'));
+        pr.next_key(KeyCode::Ctrl('r'));
+        pr.next_key(KeyCode::Char('g'));
+        assert_eq!(pr.result(), "git commit");
+        pr.next_key(KeyCode::Esc);
+        assert_eq!(pr.result(), "x");
+    }
+
+    #[test]
+    fn reverse_search_esc_without_a_match_preserves_prior_undo_history() {
+        let mut pr = PromptReader::new().with_history(vec!["git commit".into()]);
+        type_str(&mut pr, "hello");
+        pr.next_key(KeyCode::Ctrl('r'));
+        pr.next_key(KeyCode::Char('z'));
+        assert_eq!(pr.result(), "hello");
+        pr.next_key(KeyCode::Esc);
+        assert_eq!(pr.result(), "hello");
+        pr.next_key(KeyCode::Ctrl('_'));
+        assert_eq!(pr.result(), "");
+    }
+
+    #[test]
+    fn reverse_search_enter_accepts_match_without_submitting() {
+        let mut pr = PromptReader::new().with_history(vec!["git commit".into()]);
+        pr.next_key(KeyCode::Ctrl('r'));
+        pr.next_key(KeyCode::Char('g'));
+        pr.next_key(KeyCode::Enter);
+        assert_eq!(pr.result(), "git commit");
+        assert!(!pr.done());
+    }
+
+    fn type_str(pr: &mut PromptReader, s: &str) {
+        for c in s.chars() {
+            pr.next_key(KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    fn word_motion_skips_whitespace_then_word() {
+        let mut pr = PromptReader::new();
+        type_str(&mut pr, "foo bar baz");
+        pr.next_key(KeyCode::Alt('b'));
+        pr.next_key(KeyCode::Alt('b'));
+        pr.next_key(KeyCode::Char('X'));
+        assert_eq!(pr.result(), "foo Xbar baz");
+        pr.next_key(KeyCode::Alt('f'));
+        pr.next_key(KeyCode::Char('Y'));
+        assert_eq!(pr.result(), "foo XbarY baz");
+    }
+
+    #[test]
+    fn kill_to_end_of_line_and_yank() {
+        let mut pr = PromptReader::new();
+        type_str(&mut pr, "hello world");
+        pr.next_key(KeyCode::Alt('b'));
+        pr.next_key(KeyCode::Ctrl('k'));
+        assert_eq!(pr.result(), "hello ");
+        pr.next_key(KeyCode::Ctrl('y'));
+        assert_eq!(pr.result(), "hello world");
+    }
+
+    #[test]
+    fn kill_to_start_of_line() {
+        let mut pr = PromptReader::new();
+        type_str(&mut pr, "hello world");
+        pr.next_key(KeyCode::Alt('b'));
+        pr.next_key(KeyCode::Ctrl('u'));
+        assert_eq!(pr.result(), "world");
+    }
+
+    #[test]
+    fn kill_previous_word() {
+        let mut pr = PromptReader::new();
+        type_str(&mut pr, "hello world");
+        pr.next_key(KeyCode::Ctrl('w'));
+        assert_eq!(pr.result(), "hello ");
+    }
+
+    #[test]
+    fn consecutive_kills_same_direction_coalesce() {
+        let mut pr = PromptReader::new();
+        type_str(&mut pr, "four five six");
+        pr.next_key(KeyCode::Ctrl('w'));
+        pr.next_key(KeyCode::Ctrl('w'));
+        assert_eq!(pr.result(), "four ");
+        pr.next_key(KeyCode::Ctrl('y'));
+        assert_eq!(pr.result(), "four five six");
+    }
+
+    #[test]
+    fn yank_pop_cycles_to_older_ring_entry() {
+        let mut pr = PromptReader::new();
+        type_str(&mut pr, "aaa bbb");
+        pr.next_key(KeyCode::Ctrl('w'));
+        type_str(&mut pr, "ccc");
+        pr.next_key(KeyCode::Ctrl('w'));
+        // kill_ring is now ["bbb", "ccc"] (most recent last).
+        pr.next_key(KeyCode::Ctrl('y'));
+        assert_eq!(pr.result(), "aaa ccc");
+        pr.next_key(KeyCode::Alt('y'));
+        assert_eq!(pr.result(), "aaa bbb");
+    }
+
+    struct WordCompleter(Vec<&'static str>);
+
+    impl Completer for WordCompleter {
+        fn complete(&self, line: &str, cursor: usize) -> (usize, Vec<String>) {
+            let start = line[..cursor].rfind(' ').map_or(0, |i| i + 1);
+            let prefix = &line[start..cursor];
+            let candidates = self
+                .0
+                .iter()
+                .filter(|w| w.starts_with(prefix))
+                .map(|w| w.to_string())
+                .collect();
+            (start, candidates)
+        }
+    }
+
+    #[test]
+    fn tab_with_single_candidate_inserts_it_fully() {
+        let mut pr =
+            PromptReader::new().with_completer(Box::new(WordCompleter(vec!["println!"])));
+        type_str(&mut pr, "prin");
+        pr.next_key(KeyCode::Tab);
+        assert_eq!(pr.result(), "println!");
+        assert_eq!(pr.completions(), &["println!".to_string()]);
+    }
+
+    #[test]
+    fn tab_with_multiple_candidates_inserts_longest_common_prefix() {
+        let mut pr = PromptReader::new()
+            .with_completer(Box::new(WordCompleter(vec!["format!", "for", "foreach"])));
+        type_str(&mut pr, "fo");
+        pr.next_key(KeyCode::Tab);
+        assert_eq!(pr.result(), "for");
+        assert_eq!(pr.completions().len(), 3);
+    }
+
+    #[test]
+    fn tab_with_no_candidates_leaves_buffer_untouched() {
+        let mut pr = PromptReader::new().with_completer(Box::new(WordCompleter(vec!["abc"])));
+        type_str(&mut pr, "xyz");
+        pr.next_key(KeyCode::Tab);
+        assert_eq!(pr.result(), "xyz");
+        assert!(pr.completions().is_empty());
+    }
+
+    #[test]
+    fn tab_defaults_to_noop_without_a_completer() {
+        let mut pr = PromptReader::new();
+        type_str(&mut pr, "xyz");
+        pr.next_key(KeyCode::Tab);
+        assert_eq!(pr.result(), "xyz");
+    }
+
+    #[test]
+    fn ctrl_a_and_ctrl_e_move_to_home_and_end() {
+        let mut pr = PromptReader::new();
+        type_str(&mut pr, "hello");
+        pr.next_key(KeyCode::Ctrl('a'));
+        pr.next_key(KeyCode::Char('X'));
+        assert_eq!(pr.result(), "Xhello");
+        pr.next_key(KeyCode::Ctrl('e'));
+        pr.next_key(KeyCode::Char('Y'));
+        assert_eq!(pr.result(), "XhelloY");
+    }
+
+    #[test]
+    fn custom_keymap_overrides_default_binding() {
+        let mut keymap = keymap::Keymap::emacs();
+        keymap.bind(KeyCode::Ctrl('k'), Cmd::MoveHome);
+        let mut pr = PromptReader::new().with_keymap(keymap);
+        type_str(&mut pr, "hello");
+        pr.next_key(KeyCode::Ctrl('k'));
+        pr.next_key(KeyCode::Char('X'));
+        assert_eq!(pr.result(), "Xhello");
+    }
+
+    #[test]
+    fn custom_keymap_overrides_reverse_search_cycle_key() {
+        let mut keymap = keymap::Keymap::emacs();
+        keymap.bind(KeyCode::Ctrl('r'), Cmd::Noop);
+        keymap.bind(KeyCode::Ctrl('t'), Cmd::ReverseSearch);
+        let mut pr = PromptReader::new()
+            .with_keymap(keymap)
+            .with_history(vec!["git push".into(), "git pull".into()]);
+        pr.next_key(KeyCode::Ctrl('t'));
+        pr.next_key(KeyCode::Char('g'));
+        assert_eq!(pr.result(), "git pull");
+        pr.next_key(KeyCode::Ctrl('t'));
+        assert_eq!(pr.result(), "git push");
+    }
+
+    #[test]
+    fn undo_reverts_a_coalesced_word_insert_as_one_unit() {
+        let mut pr = PromptReader::new();
+        type_str(&mut pr, "hello");
+        pr.next_key(KeyCode::Ctrl('_'));
+        assert_eq!(pr.result(), "");
+    }
+
+    #[test]
+    fn undo_then_redo_restores_the_edit() {
+        let mut pr = PromptReader::new();
+        type_str(&mut pr, "hello");
+        pr.next_key(KeyCode::Ctrl('_'));
+        assert_eq!(pr.result(), "");
+        pr.next_key(KeyCode::Alt('_'));
+        assert_eq!(pr.result(), "hello");
+    }
+
+    #[test]
+    fn cursor_movement_breaks_the_insert_group() {
+        let mut pr = PromptReader::new();
+        type_str(&mut pr, "foo");
+        pr.next_key(KeyCode::Left);
+        type_str(&mut pr, "bar");
+        // "bar" coalesces into its own group, undoing once should only remove it.
+        pr.next_key(KeyCode::Ctrl('_'));
+        assert_eq!(pr.result(), "foo");
+    }
+
+    #[test]
+    fn whitespace_breaks_the_insert_group() {
+        let mut pr = PromptReader::new();
+        type_str(&mut pr, "foo bar");
+        pr.next_key(KeyCode::Ctrl('_'));
+        assert_eq!(pr.result(), "foo ");
+        pr.next_key(KeyCode::Ctrl('_'));
+        assert_eq!(pr.result(), "foo");
+        pr.next_key(KeyCode::Ctrl('_'));
+        assert_eq!(pr.result(), "");
+    }
+
+    #[test]
+    fn undo_reverts_a_backspace() {
+        let mut pr = PromptReader::new();
+        type_str(&mut pr, "hej");
+        pr.next_key(KeyCode::Backspace);
+        assert_eq!(pr.result(), "he");
+        pr.next_key(KeyCode::Ctrl('_'));
+        assert_eq!(pr.result(), "hej");
+    }
+
+    #[test]
+    fn new_edit_after_undo_clears_redo_stack() {
+        let mut pr = PromptReader::new();
+        type_str(&mut pr, "foo");
+        pr.next_key(KeyCode::Ctrl('_'));
+        assert_eq!(pr.result(), "");
+        pr.next_key(KeyCode::Char('x'));
+        pr.next_key(KeyCode::Alt('_'));
+        assert_eq!(pr.result(), "x");
+    }
+
+    #[test]
+    fn paste_inserts_newlines_literally_without_submitting() {
+        let mut pr = PromptReader::new();
+        pr.paste("line one\nline two");
+        assert_eq!(pr.result(), "line one\nline two");
+        assert!(!pr.done());
+    }
+
+    #[test]
+    fn paste_advances_cursor_to_end_of_inserted_text() {
+        let mut pr = PromptReader::new();
+        type_str(&mut pr, "ab");
+        pr.next_key(KeyCode::Left);
+        pr.paste("XYZ");
+        pr.next_key(KeyCode::Char('!'));
+        assert_eq!(pr.result(), "aXYZ!b");
+    }
+
+    #[test]
+    fn paste_is_a_single_undo_group() {
+        let mut pr = PromptReader::new();
+        type_str(&mut pr, "ab");
+        pr.paste("123");
+        assert_eq!(pr.result(), "ab123");
+        pr.next_key(KeyCode::Ctrl('_'));
+        assert_eq!(pr.result(), "ab");
+    }
+
+    #[test]
+    fn paste_key_code_bypasses_search_mode() {
+        let mut pr = PromptReader::new().with_history(vec!["git commit".into()]);
+        pr.next_key(KeyCode::Ctrl('r'));
+        pr.next_key(KeyCode::Paste("pasted".into()));
+        assert_eq!(pr.result(), "pasted");
+    }
+
+    #[test]
+    fn paste_filter_control_policy_drops_control_chars_but_keeps_newline() {
+        let mut pr = PromptReader::new().with_paste_policy(PastePolicy::FilterControl);
+        pr.paste("a\u{7}b\nc");
+        assert_eq!(pr.result(), "ab\nc");
+    }
 }